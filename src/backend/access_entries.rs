@@ -0,0 +1,188 @@
+use super::{AuthBackend, Mapping};
+use anyhow::Context;
+use aws_sdk_eks::types::{AccessEntry, AccessEntryType};
+use futures::future::join_all;
+use std::env;
+use tracing::log;
+
+/// Tag written on every access entry this controller creates, so `list`/`sync` only ever consider
+/// entries they own. Without this, `list_access_entries` would also return entries created by EKS
+/// itself (managed node groups, Fargate profiles, the cluster-creator admin entry), which must
+/// never be touched here.
+const OWNER_TAG_KEY: &str = "aws-eks-iam-auth-controller.rustrial.org/managed-by";
+
+const OWNER_TAG_VALUE: &str = "aws-eks-iam-auth-controller";
+
+/// The modern replacement for the `aws-auth` ConfigMap: manages principals via the EKS Access
+/// Entries API, for clusters that have `authenticationMode: API` (or `API_AND_CONFIG_MAP`) and
+/// may have the `aws-auth` ConfigMap disabled entirely.
+pub struct AccessEntryBackend {
+    client: aws_sdk_eks::Client,
+    cluster_name: String,
+}
+
+impl AccessEntryBackend {
+    pub fn new(client: aws_sdk_eks::Client, cluster_name: String) -> Self {
+        Self {
+            client,
+            cluster_name,
+        }
+    }
+
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let cluster_name = env::var("EKS_CLUSTER_NAME")
+            .context("EKS_CLUSTER_NAME must be set when AUTH_BACKEND=access-entries")?;
+        let config = aws_config::load_from_env().await;
+        Ok(Self::new(aws_sdk_eks::Client::new(&config), cluster_name))
+    }
+
+    /// Lists every principal ARN with an access entry on this cluster, paging through
+    /// `list_access_entries` until `next_token` is exhausted. A cluster with more entries than fit
+    /// on one page would otherwise leave later-page entries invisible to `list`/`sync`: they'd
+    /// never be updated, and a desired mapping sitting on a later page would be re-created and hit
+    /// `ResourceInUseException`.
+    async fn list_all_principal_arns(&self) -> Vec<String> {
+        let mut principal_arns = Vec::new();
+        let mut next_token = None;
+        loop {
+            let page = self
+                .client
+                .list_access_entries()
+                .cluster_name(&self.cluster_name)
+                .set_next_token(next_token)
+                .send()
+                .await;
+            log::info!("Got existing access entries page: {:?}", page);
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    log::warn!("Failed to list access entries: {}", e);
+                    break;
+                }
+            };
+            principal_arns.extend(page.access_entries.unwrap_or_default());
+            next_token = page.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        principal_arns
+    }
+
+    async fn describe_access_entry(&self, principal_arn: &str) -> Option<AccessEntry> {
+        self.client
+            .describe_access_entry()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(principal_arn)
+            .send()
+            .await
+            .ok()
+            .and_then(|o| o.access_entry)
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for AccessEntryBackend {
+    async fn list(&self) -> Vec<Mapping> {
+        let principal_arns = self.list_all_principal_arns().await;
+        let described = join_all(
+            principal_arns
+                .iter()
+                .map(|principal_arn| self.describe_access_entry(principal_arn)),
+        )
+        .await;
+        let mut mappings = Vec::with_capacity(principal_arns.len());
+        for (principal_arn, entry) in principal_arns.into_iter().zip(described) {
+            match entry {
+                // Only STANDARD entries tagged as ours are in scope: this excludes the
+                // EC2_LINUX/EC2_WINDOWS/FARGATE_LINUX entries EKS creates for node groups and
+                // Fargate profiles, and the cluster-creator admin entry, none of which this
+                // controller is allowed to delete.
+                Some(entry)
+                    if entry.r#type.as_ref() == Some(&AccessEntryType::Standard)
+                        && entry
+                            .tags
+                            .as_ref()
+                            .and_then(|t| t.get(OWNER_TAG_KEY))
+                            .map(|v| v == OWNER_TAG_VALUE)
+                            .unwrap_or(false) =>
+                {
+                    mappings.push(Mapping {
+                        arn: entry.principal_arn.unwrap_or(principal_arn),
+                        username: entry.username.unwrap_or_default(),
+                        groups: entry.kubernetes_groups.unwrap_or_default(),
+                    })
+                }
+                Some(_) => log::trace!("Ignoring access entry not owned by this controller: {}", principal_arn),
+                None => log::warn!("Could not describe access entry for {}", principal_arn),
+            }
+        }
+        mappings
+    }
+
+    async fn sync(&self, desired: &[Mapping]) -> anyhow::Result<()> {
+        let existing = self.list().await;
+
+        // Remove access entries which have no corresponding desired mapping. `existing` is
+        // already scoped to entries this controller owns, so this never touches node group,
+        // Fargate, or admin entries.
+        for entry in existing.iter().filter(|e| !desired.iter().any(|d| d.arn == e.arn)) {
+            log::info!("Deleting access entry for {}", entry.arn);
+            self.client
+                .delete_access_entry()
+                .cluster_name(&self.cluster_name)
+                .principal_arn(&entry.arn)
+                .send()
+                .await
+                .with_context(|| format!("Failed to delete access entry for {}", entry.arn))?;
+        }
+
+        // Create/update access entries for the desired mappings.
+        for mapping in desired {
+            if existing.iter().any(|e| e.arn == mapping.arn) {
+                log::info!("Updating access entry for {}", mapping.arn);
+                self.client
+                    .update_access_entry()
+                    .cluster_name(&self.cluster_name)
+                    .principal_arn(&mapping.arn)
+                    .username(&mapping.username)
+                    .set_kubernetes_groups(Some(mapping.groups.clone()))
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to update access entry for {}", mapping.arn))?;
+            } else {
+                log::info!("Creating access entry for {}", mapping.arn);
+                self.client
+                    .create_access_entry()
+                    .cluster_name(&self.cluster_name)
+                    .principal_arn(&mapping.arn)
+                    .username(&mapping.username)
+                    .set_kubernetes_groups(Some(mapping.groups.clone()))
+                    .tags(OWNER_TAG_KEY, OWNER_TAG_VALUE)
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to create access entry for {}", mapping.arn))?;
+                // Access policy association is opt-in: unlike the ConfigMap backend (which grants
+                // nothing beyond the k8s groups), associating a policy here would be assigning
+                // privileges nobody asked for. Set ACCESS_ENTRY_POLICY_ARN to associate the same
+                // policy with every entry this controller creates.
+                if let Ok(policy_arn) = env::var("ACCESS_ENTRY_POLICY_ARN") {
+                    self.client
+                        .associate_access_policy()
+                        .cluster_name(&self.cluster_name)
+                        .principal_arn(&mapping.arn)
+                        .policy_arn(&policy_arn)
+                        .access_scope(
+                            aws_sdk_eks::types::AccessScope::builder()
+                                .r#type(aws_sdk_eks::types::AccessScopeType::Cluster)
+                                .build(),
+                        )
+                        .send()
+                        .await
+                        .with_context(|| format!("Failed to associate access policy for {}", mapping.arn))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
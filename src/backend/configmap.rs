@@ -0,0 +1,148 @@
+use super::{AuthBackend, Mapping};
+use anyhow::Context;
+use k8s_openapi::{api::core::v1::ConfigMap, apimachinery::pkg::apis::meta::v1::ObjectMeta};
+use kube::{
+    api::{Patch, PatchParams, ValidationDirective},
+    Api, Client,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::log;
+
+const AWS_AUTH: &str = "aws-auth";
+
+const KUBE_SYSTEM: &str = "kube-system";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct MapRole {
+    pub rolearn: String,
+    pub username: String,
+    pub groups: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct MapUser {
+    pub userarn: String,
+    pub username: String,
+    pub groups: Option<Vec<String>>,
+}
+
+/// The original backend: manipulates the `aws-auth` ConfigMap in `kube-system`, as consumed by
+/// the [aws-iam-authenticator project](https://github.com/kubernetes-sigs/aws-iam-authenticator).
+pub struct ConfigMapBackend {
+    cm_api: Api<ConfigMap>,
+}
+
+impl ConfigMapBackend {
+    pub fn new(client: Client) -> Self {
+        Self {
+            cm_api: Api::namespaced(client, KUBE_SYSTEM),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for ConfigMapBackend {
+    async fn list(&self) -> Vec<Mapping> {
+        let cm = self.cm_api.get(AWS_AUTH).await;
+        log::info!("Got existing ConfigMap: {:?}", cm);
+        let (roles, users) = parse_config_map(cm.ok()).unwrap_or_default();
+        roles
+            .into_iter()
+            .map(|r| Mapping {
+                arn: r.rolearn,
+                username: r.username,
+                groups: r.groups.unwrap_or_default(),
+            })
+            .chain(users.into_iter().map(|u| Mapping {
+                arn: u.userarn,
+                username: u.username,
+                groups: u.groups.unwrap_or_default(),
+            }))
+            .collect()
+    }
+
+    async fn sync(&self, desired: &[Mapping]) -> anyhow::Result<()> {
+        let cm = self.cm_api.get(AWS_AUTH).await;
+        log::info!("Got existing ConfigMap: {:?}", cm);
+        let (mut roles, mut users) = parse_config_map(cm.ok())?;
+
+        // Remove all ConfigMap entries, which have no corresponding desired mapping.
+        roles.retain(|r| desired.iter().any(|v| r.rolearn == v.arn));
+        users.retain(|r| desired.iter().any(|v| r.userarn == v.arn));
+        // Upsert (add/update) ConfigMap entries for the desired mappings.
+        for mapping in desired {
+            if mapping.is_role() {
+                // optionally, remove already existing ConfigMap entry.
+                roles.retain(|r| r.rolearn != mapping.arn);
+                roles.push(MapRole {
+                    rolearn: mapping.arn.clone(),
+                    username: mapping.username.clone(),
+                    groups: Some(mapping.groups.clone()),
+                });
+            } else {
+                // optionally, remove already existing ConfigMap entry.
+                users.retain(|r| r.userarn != mapping.arn);
+                users.push(MapUser {
+                    userarn: mapping.arn.clone(),
+                    username: mapping.username.clone(),
+                    groups: Some(mapping.groups.clone()),
+                });
+            }
+        }
+        let mut contents = BTreeMap::new();
+        contents.insert(
+            "mapRoles".to_string(),
+            serde_yaml::to_string(&roles).context("Error while serializing mapRoles")?,
+        );
+        contents.insert(
+            "mapUsers".to_string(),
+            serde_yaml::to_string(&users).context("Error while serializing mapUsers")?,
+        );
+        let cm = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(AWS_AUTH.to_string()),
+                namespace: Some(KUBE_SYSTEM.to_string()),
+                ..ObjectMeta::default()
+            },
+            data: Some(contents),
+            ..Default::default()
+        };
+        log::info!("ConfigMap changeset: {:?}", cm);
+        self.cm_api
+            .patch(
+                AWS_AUTH,
+                &PatchParams {
+                    field_manager: Some("aws-eks-iam-auth-controller.rustrial.org".to_string()),
+                    dry_run: false,
+                    force: true,
+                    field_validation: Some(ValidationDirective::Ignore),
+                },
+                &Patch::Apply(cm),
+            )
+            .await
+            .context("Failed to create ConfigMap")?;
+        Ok(())
+    }
+}
+
+fn parse_config_map(cm: Option<ConfigMap>) -> anyhow::Result<(Vec<MapRole>, Vec<MapUser>)> {
+    let (roles, users) = cm
+        .and_then(|v| v.data)
+        .map(|d| {
+            (
+                d.get("mapRoles")
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "[]".to_string()),
+                d.get("mapUsers")
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "[]".to_string()),
+            )
+        })
+        .unwrap_or_else(|| ("[]".to_string(), "[]".to_string()));
+    let roles: Vec<MapRole> =
+        serde_yaml::from_str(roles.as_str()).context("Error while deserializing mapRoles")?;
+    let users: Vec<MapUser> =
+        serde_yaml::from_str(users.as_str()).context("Error while deserializing mapUsers")?;
+    Ok((roles, users))
+}
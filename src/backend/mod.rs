@@ -0,0 +1,48 @@
+mod access_entries;
+mod configmap;
+
+pub use access_entries::AccessEntryBackend;
+pub use configmap::ConfigMapBackend;
+
+/// A single identity mapping, translated from an [`IAMIdentityMappingSpec`](crate::IAMIdentityMappingSpec)
+/// into the shape every [`AuthBackend`] operates on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub arn: String,
+    pub username: String,
+    pub groups: Vec<String>,
+}
+
+impl Mapping {
+    /// Whether this mapping's ARN refers to an IAM role (as opposed to an IAM user).
+    pub fn is_role(&self) -> bool {
+        self.arn.contains(":role/")
+    }
+}
+
+/// Storage abstraction for where IAM identity mappings actually get persisted, so `reconcile`
+/// doesn't need to know whether it's talking to the `aws-auth` ConfigMap or EKS Access Entries.
+///
+/// Implementations are expected to be cheap to clone/share (e.g. wrap a `kube::Client` or AWS SDK
+/// client, both of which are already `Arc`-backed handles).
+#[async_trait::async_trait]
+pub trait AuthBackend: Send + Sync {
+    /// Returns the mappings currently known to the backend. Errors are swallowed to an empty
+    /// list, matching the original ConfigMap-reading behaviour of tolerating a missing backend.
+    async fn list(&self) -> Vec<Mapping>;
+
+    /// Makes the backend's state match `desired` exactly: mappings not present in `desired` are
+    /// removed, and every mapping in `desired` is created or updated.
+    async fn sync(&self, desired: &[Mapping]) -> anyhow::Result<()>;
+}
+
+/// Selects the [`AuthBackend`] implementation based on the `AUTH_BACKEND` environment variable.
+/// Defaults to `configmap` (the original aws-auth ConfigMap behaviour) when unset.
+pub async fn from_env(client: kube::Client) -> anyhow::Result<std::sync::Arc<dyn AuthBackend>> {
+    let backend = std::env::var("AUTH_BACKEND").unwrap_or_else(|_| "configmap".to_string());
+    match backend.trim().to_lowercase().as_str() {
+        "access-entries" => Ok(std::sync::Arc::new(AccessEntryBackend::from_env().await?)),
+        "configmap" | "" => Ok(std::sync::Arc::new(ConfigMapBackend::new(client))),
+        other => anyhow::bail!("Unknown AUTH_BACKEND {:?}, expected \"configmap\" or \"access-entries\"", other),
+    }
+}
@@ -0,0 +1,34 @@
+use crate::Data;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use std::sync::Arc;
+
+/// Runs the liveness/readiness HTTP server on `port` until the process is stopped.
+///
+/// `/healthz` reports ok as soon as the tokio runtime is up; `/readyz` reports ok once leader
+/// election has made its first attempt at the `Lease`. This intentionally doesn't wait on the
+/// reflector `Store` or a successful reconcile: both depend on holding leadership, so a standby
+/// replica in an HA deployment would never pass them and would stay NotReady for as long as it
+/// isn't leader.
+pub async fn run(port: u16, ctx: Arc<Data>) -> std::io::Result<()> {
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(ctx.clone()))
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+    })
+    .bind(("0.0.0.0", port))?
+    .run()
+    .await
+}
+
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+async fn readyz(ctx: web::Data<Arc<Data>>) -> HttpResponse {
+    if ctx.is_ready() {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::ServiceUnavailable().finish()
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::Utc;
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use kube::api::PostParams;
+use kube::{Api, Client};
+use metrics::gauge;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::log;
+
+const DEFAULT_LEASE_NAME: &str = "aws-eks-iam-auth-controller-leader";
+
+const DEFAULT_LEASE_NAMESPACE: &str = "kube-system";
+
+const LEASE_DURATION_SECONDS: i32 = 15;
+
+/// Coordinates leadership across replicas via a `coordination.k8s.io/v1` `Lease`, so only one
+/// replica drives the `Controller` reconcile loop at a time while the others stand by.
+pub struct LeaderElector {
+    api: Api<Lease>,
+    lease_name: String,
+    identity: String,
+}
+
+impl LeaderElector {
+    /// Builds a `LeaderElector` from the `LEASE_NAME`/`LEASE_NAMESPACE` environment variables,
+    /// defaulting to `aws-eks-iam-auth-controller-leader` in `kube-system`.
+    pub fn from_env(client: Client) -> Self {
+        let namespace =
+            env::var("LEASE_NAMESPACE").unwrap_or_else(|_| DEFAULT_LEASE_NAMESPACE.to_string());
+        let lease_name = env::var("LEASE_NAME").unwrap_or_else(|_| DEFAULT_LEASE_NAME.to_string());
+        let identity = env::var("POD_NAME").unwrap_or_else(|_| {
+            let hostname = env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string());
+            format!("{}-{}", hostname, std::process::id())
+        });
+        Self {
+            api: Api::namespaced(client, &namespace),
+            lease_name,
+            identity,
+        }
+    }
+
+    /// Spawns a background task that continuously attempts to acquire/renew the `Lease`,
+    /// publishing the current leadership state (and the `leader` gauge) on the returned channel.
+    /// `observed` is flipped to `true` after the first attempt, win or lose, so callers that only
+    /// care whether leader election has started (e.g. readiness) don't have to wait on leadership
+    /// itself, which a standby replica may never hold.
+    pub fn spawn(self, observed: Arc<AtomicBool>) -> watch::Receiver<bool> {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move { self.run(tx, observed).await });
+        rx
+    }
+
+    async fn run(&self, tx: watch::Sender<bool>, observed: Arc<AtomicBool>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(LEASE_DURATION_SECONDS as u64 / 3));
+        loop {
+            interval.tick().await;
+            let leading = match self.try_acquire_or_renew().await {
+                Ok(leading) => leading,
+                Err(e) => {
+                    log::warn!("Leader election error: {}", e);
+                    false
+                }
+            };
+            gauge!("leader", if leading { 1.0 } else { 0.0 });
+            let _ = tx.send(leading);
+            observed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Attempts to become (or remain) the leader.
+    ///
+    /// Acquisition/renewal goes through `create`/`replace` rather than a forced server-side
+    /// apply: `create` only succeeds for whoever gets there first on a missing Lease, and
+    /// `replace` carries the `resourceVersion` we just read, so the API server rejects it with a
+    /// 409 if another replica already renewed in between. That optimistic-concurrency check is
+    /// what a force-apply (which always wins) can't give us, and is what prevents two replicas
+    /// both believing they hold an expired lease.
+    async fn try_acquire_or_renew(&self) -> anyhow::Result<bool> {
+        let now = Utc::now();
+        match self.api.get_opt(&self.lease_name).await? {
+            None => {
+                let lease = self.new_lease(now, Some(MicroTime(now)));
+                match self.api.create(&PostParams::default(), &lease).await {
+                    Ok(_) => Ok(true),
+                    Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Some(existing) => {
+                let spec = existing.spec.clone().unwrap_or_default();
+                let holder = spec.holder_identity.clone().unwrap_or_default();
+                let renew_time = spec.renew_time.clone().map(|t| t.0).unwrap_or(now);
+                let duration = chrono::Duration::seconds(
+                    spec.lease_duration_seconds.unwrap_or(LEASE_DURATION_SECONDS) as i64,
+                );
+                let expired = now - renew_time > duration;
+                if holder != self.identity && !expired {
+                    return Ok(false);
+                }
+                let acquire_time = if holder == self.identity {
+                    spec.acquire_time.clone()
+                } else {
+                    Some(MicroTime(now))
+                };
+                let mut lease = self.new_lease(now, acquire_time);
+                lease.metadata.resource_version = existing.metadata.resource_version.clone();
+                match self.api.replace(&self.lease_name, &PostParams::default(), &lease).await {
+                    Ok(_) => Ok(true),
+                    Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    fn new_lease(&self, now: chrono::DateTime<Utc>, acquire_time: Option<MicroTime>) -> Lease {
+        Lease {
+            metadata: ObjectMeta {
+                name: Some(self.lease_name.clone()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: Some(self.identity.clone()),
+                lease_duration_seconds: Some(LEASE_DURATION_SECONDS),
+                acquire_time,
+                renew_time: Some(MicroTime(now)),
+                ..Default::default()
+            }),
+        }
+    }
+}
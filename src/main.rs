@@ -1,18 +1,23 @@
+mod backend;
+mod health;
+mod leader;
+mod webhook;
+
+use anyhow::Context;
 use tracing::log;
 use tracing_subscriber;
 use tracing_subscriber::filter::{
     EnvFilter,
     LevelFilter,
 };
-use anyhow::Context;
-use futures::StreamExt;
-use k8s_openapi::{api::core::v1::ConfigMap, apimachinery::pkg::apis::meta::v1::ObjectMeta};
+use backend::{AuthBackend, Mapping};
 use kube::{
-    api::{Patch, PatchParams, ValidationDirective},
-    Api, Client, CustomResource,
+    api::{Patch, PatchParams},
+    Api, Client, CustomResource, Resource,
 };
 use kube_runtime::{
     controller::{Action, Controller},
+    finalizer::{finalizer, Event},
     reflector::Store,
     watcher::Config,
 };
@@ -20,13 +25,13 @@ use metrics::{counter, gauge, histogram};
 use metrics_exporter_prometheus::PrometheusBuilder;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{env, collections::BTreeMap, sync::Arc, time::Instant};
+use std::{
+    env,
+    sync::{atomic::AtomicBool, atomic::Ordering, Arc},
+    time::Instant,
+};
 use tokio::time::Duration;
 
-const AWS_AUTH: &str = "aws-auth";
-
-const KUBE_SYSTEM: &str = "kube-system";
-
 #[derive(thiserror::Error, Debug)]
 enum CrdError {
     #[error("{0}")]
@@ -55,116 +60,151 @@ struct IAMIdentityMappingSpec {
     groups: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema, Default)]
 struct IAMIdentityMappingStatus {
+    /// High-level outcome of the last sync attempt, e.g. `"Synced"` or `"Error"`.
     status: String,
+    /// The ARN that was last observed for this mapping, useful to confirm a spec edit landed.
+    observed_arn: Option<String>,
+    /// When the mapping was last successfully synced to the auth backend. The status is only
+    /// patched when its other fields change (see `record_status`), so repeated successful syncs
+    /// leave the earlier time in place; an `"Error"` status keeps the previous value.
+    last_synced: Option<chrono::DateTime<chrono::Utc>>,
+    /// Human-readable detail, populated with the error message when `status == "Error"`.
+    message: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct MapRole {
-    pub rolearn: String,
-    pub username: String,
-    pub groups: Option<Vec<String>>,
-}
+/// Name of the finalizer we attach to every `IAMIdentityMapping`, so that deletion always goes
+/// through `cleanup` and removes exactly that mapping's entry from the auth backend.
+const FINALIZER_NAME: &str = "aws-eks-iam-auth-controller.rustrial.org/cleanup";
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct MapUser {
-    pub userarn: String,
-    pub username: String,
-    pub groups: Option<Vec<String>>,
+/// Controller triggers this whenever our main object or our children changed. Deletion is
+/// handled through a finalizer so that removing the last surviving mapping is guaranteed to
+/// trigger `cleanup`, rather than relying on some other object's reconcile to notice the diff.
+async fn reconcile(mapping: Arc<IAMIdentityMapping>, ctx: Arc<Data>) -> Result<Action, CrdError> {
+    // IAMIdentityMapping is cluster-scoped (matching upstream aws-iam-authenticator), so there is
+    // no namespace to key the Api off of.
+    let api: Api<IAMIdentityMapping> = Api::all(ctx.client.clone());
+    finalizer(&api, FINALIZER_NAME, mapping, |event| async {
+        match event {
+            Event::Apply(mapping) => apply(mapping, ctx.clone()).await,
+            Event::Cleanup(mapping) => cleanup(mapping, ctx.clone()).await,
+        }
+    })
+    .await
+    .map_err(|e| CrdError::Any(e.to_string()))
 }
 
-/// Controller triggers this whenever our main object or our children changed
-async fn reconcile(mapping: Arc<IAMIdentityMapping>, ctx: Arc<Data>) -> Result<Action, CrdError> {
+/// Syncs the full desired state (every mapping currently in the store) to the auth backend, then
+/// records the outcome in the reconciled mapping's own status.
+async fn apply(mapping: Arc<IAMIdentityMapping>, ctx: Arc<Data>) -> Result<Action, CrdError> {
     let start = Instant::now();
     log::info!("reconile {:?}", mapping);
-    let client = ctx.as_ref().client.clone();
-    let cm_api = Api::<ConfigMap>::namespaced(client.clone(), KUBE_SYSTEM);
-    let cm = cm_api.get(AWS_AUTH).await;
-    log::info!("Got existing ConfigMap: {:?}", cm);
-    let cm = cm.ok();
-
-    let (roles, users) = cm
-        .map(|v| v.data)
-        .flatten()
-        .map(|d| {
-            (
-                d.get("mapRoles")
-                    .map(|v| v.to_string())
-                    .unwrap_or_else(|| "[]".to_string()),
-                d.get("mapUsers")
-                    .map(|v| v.to_string())
-                    .unwrap_or_else(|| "[]".to_string()),
-            )
-        })
-        .unwrap_or_else(|| ("[]".to_string(), "[]".to_string()));
-    let mut roles: Vec<MapRole> =
-        serde_yaml::from_str(roles.as_str()).context("Error while deserializing mapRoles")?;
-    let mut users: Vec<MapUser> =
-        serde_yaml::from_str(users.as_str()).context("Error while deserializing mapUsers")?;
 
     let state: Vec<Arc<IAMIdentityMapping>> = ctx.as_ref().store.clone().state();
-    // Remove all ConfitMap entries, which have no corresponding CustomResource.
-    roles.retain(|r| state.iter().find(|v| r.rolearn == v.spec.arn).is_some());
-    users.retain(|r| state.iter().find(|v| r.username == v.spec.arn).is_some());
-    // Upsert (add/update) ConfigMap entries for CustomerResources.
-    for item in state {
-        let spec: &IAMIdentityMappingSpec = &item.spec;
-        if spec.arn.contains(":role/") {
-            // optionally, remove already existing ConfigMap entry.
-            roles.retain(|r| r.rolearn != spec.arn);
-            roles.push(MapRole {
-                rolearn: spec.arn.clone(),
-                username: spec.username.clone(),
-                groups: spec.groups.clone(),
-            });
-        } else {
-            // optionally, remove already existing ConfigMap entry.
-            users.retain(|r| r.userarn != spec.arn);
-            users.push(MapUser {
-                userarn: spec.arn.clone(),
-                username: spec.username.clone(),
-                groups: spec.groups.clone(),
-            });
-        }
-    }
-    let mut contents = BTreeMap::new();
-    contents.insert(
-        "mapRoles".to_string(),
-        serde_yaml::to_string(&roles).context("Error while serializing mapRoles")?,
-    );
-    contents.insert(
-        "mapUsers".to_string(),
-        serde_yaml::to_string(&users).context("Error while serializing mapUsers")?,
-    );
-    let cm = ConfigMap {
-        metadata: ObjectMeta {
-            name: Some(AWS_AUTH.to_string()),
-            namespace: Some(KUBE_SYSTEM.to_string()),
-            ..ObjectMeta::default()
-        },
-        data: Some(contents),
-        ..Default::default()
-    };
-    log::info!("ConfigMap changeset: {:?}", cm);
-    cm_api
-        .patch(
-            AWS_AUTH,
-            &PatchParams {
-                field_manager: Some("aws-eks-iam-auth-controller.rustrial.org".to_string()),
-                dry_run: false,
-                force: true,
-                field_validation: Some(ValidationDirective::Ignore),
-            },
-            &Patch::Apply(cm),
-        )
-        .await
-        .context("Failed to create ConfigMap")?;
+    let desired: Vec<Mapping> = state.iter().map(|item| to_mapping(&item.spec)).collect();
+    let sync_result = ctx.as_ref().backend.sync(&desired).await;
+
+    record_status(&ctx, &mapping, &sync_result).await;
+
+    sync_result.context("Failed to sync auth backend")?;
     let duration = Instant::now() - start;
     histogram!("reconcile_duration_ns", duration.as_nanos() as f64);
     Ok(Action::requeue(Duration::from_secs(900)))
 }
 
+/// Removes exactly this mapping's entry from the auth backend by syncing the desired state
+/// without it, then lets the finalizer be removed so deletion can proceed.
+async fn cleanup(mapping: Arc<IAMIdentityMapping>, ctx: Arc<Data>) -> Result<Action, CrdError> {
+    log::info!("cleanup {:?}", mapping);
+    let state: Vec<Arc<IAMIdentityMapping>> = ctx.as_ref().store.clone().state();
+    let desired: Vec<Mapping> = state
+        .iter()
+        .filter(|item| item.meta().uid != mapping.meta().uid)
+        .map(|item| to_mapping(&item.spec))
+        .collect();
+    ctx.as_ref()
+        .backend
+        .sync(&desired)
+        .await
+        .context("Failed to remove mapping from auth backend")?;
+    Ok(Action::await_change())
+}
+
+fn to_mapping(spec: &IAMIdentityMappingSpec) -> Mapping {
+    Mapping {
+        arn: spec.arn.clone(),
+        username: spec.username.clone(),
+        groups: spec.groups.clone().unwrap_or_default(),
+    }
+}
+
+async fn record_status(ctx: &Arc<Data>, item: &IAMIdentityMapping, sync_result: &anyhow::Result<()>) {
+    let status = match sync_result {
+        Ok(()) => IAMIdentityMappingStatus {
+            status: "Synced".to_string(),
+            observed_arn: Some(item.spec.arn.clone()),
+            last_synced: Some(chrono::Utc::now()),
+            message: None,
+        },
+        Err(e) => IAMIdentityMappingStatus {
+            status: "Error".to_string(),
+            observed_arn: Some(item.spec.arn.clone()),
+            last_synced: item.status.as_ref().and_then(|s| s.last_synced),
+            message: Some(e.to_string()),
+        },
+    };
+    // last_synced always ticks forward, so compare everything else first: otherwise every
+    // reconcile (every 900s, per object) would bump resourceVersion and re-trigger the watcher
+    // for no observable change in outcome.
+    if status_unchanged(item.status.as_ref(), &status) {
+        return;
+    }
+    if let Err(e) = update_status(&ctx.client, item, status).await {
+        log::warn!(
+            "Failed to update status for {:?}/{:?}: {}",
+            item.meta().namespace,
+            item.meta().name,
+            e
+        );
+    }
+}
+
+fn status_unchanged(current: Option<&IAMIdentityMappingStatus>, new: &IAMIdentityMappingStatus) -> bool {
+    match current {
+        Some(current) => {
+            current.status == new.status
+                && current.observed_arn == new.observed_arn
+                && current.message == new.message
+        }
+        None => false,
+    }
+}
+
+/// Patches the `status` subresource of a single `IAMIdentityMapping` so
+/// `kubectl get iamidentitymapping` reflects whether it actually made it into the auth backend.
+async fn update_status(
+    client: &Client,
+    mapping: &IAMIdentityMapping,
+    status: IAMIdentityMappingStatus,
+) -> anyhow::Result<()> {
+    let name = mapping
+        .meta()
+        .name
+        .clone()
+        .context("IAMIdentityMapping has no name")?;
+    // IAMIdentityMapping is cluster-scoped, same as in `reconcile`.
+    let api: Api<IAMIdentityMapping> = Api::all(client.clone());
+    api.patch_status(
+        &name,
+        &PatchParams::default(),
+        &Patch::Merge(serde_json::json!({ "status": status })),
+    )
+    .await
+    .context("Failed to patch IAMIdentityMapping status")?;
+    Ok(())
+}
+
 /// The controller triggers this on reconcile errors
 fn error_policy(_object: Arc<IAMIdentityMapping>, _error: &CrdError, _ctx: Arc<Data>) -> Action {
     Action::requeue(Duration::from_secs(10))
@@ -174,6 +214,20 @@ fn error_policy(_object: Arc<IAMIdentityMapping>, _error: &CrdError, _ctx: Arc<D
 struct Data {
     client: Client,
     store: Store<IAMIdentityMapping>,
+    backend: Arc<dyn AuthBackend>,
+    lease_observed: Arc<AtomicBool>,
+}
+
+impl Data {
+    /// Ready once leader election has made its first attempt at the `Lease`. This deliberately
+    /// does not wait on the reflector `Store`: the `Store` is only populated while the `Controller`
+    /// future is polled, which a standby replica never does (see the leader-gating loop in
+    /// `main`), so gating readiness on it would leave every standby permanently NotReady. Gating on
+    /// "at least one successful reconcile" has the same problem, plus it leaves an empty cluster
+    /// (nothing to reconcile) permanently NotReady too.
+    fn is_ready(&self) -> bool {
+        self.lease_observed.load(Ordering::Relaxed)
+    }
 }
 
 async fn scheduled_statistics(store: Store<IAMIdentityMapping>) {
@@ -204,12 +258,23 @@ async fn main() -> anyhow::Result<()> {
     let metrics_builder = PrometheusBuilder::new();
     metrics_builder.install()?;
     let client = Client::try_default().await?;
+    let backend = backend::from_env(client.clone())
+        .await
+        .context("Failed to initialize auth backend")?;
+    let lease_observed = Arc::new(AtomicBool::new(false));
+    let mut leader_rx = leader::LeaderElector::from_env(client.clone()).spawn(lease_observed.clone());
     let iam_identity_mappings = Api::<IAMIdentityMapping>::all(client.clone());
     let controller = Controller::new(iam_identity_mappings, Config::default());
     let store = controller.store();
     let schedule = tokio::spawn(scheduled_statistics(store.clone()));
+    let ctx = Arc::new(Data {
+        client,
+        store: store.clone(),
+        backend,
+        lease_observed,
+    });
     let controller = controller
-        .run(reconcile, error_policy, Arc::new(Data { client, store }))
+        .run(reconcile, error_policy, ctx.clone())
         .for_each(|res| async move {
             match res {
                 Ok(o) => {
@@ -222,9 +287,56 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         });
+    // Only drive the controller while we hold the Lease; standby replicas just watch for a
+    // leadership change instead of also hitting the auth backend.
+    tokio::pin!(controller);
+    let controller = async {
+        loop {
+            if *leader_rx.borrow() {
+                tokio::select! {
+                    _ = &mut controller => break,
+                    changed = leader_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        if !*leader_rx.borrow() {
+                            log::info!("Lost leadership, pausing reconciliation");
+                        }
+                    }
+                }
+            } else {
+                log::info!("Not leader, standing by");
+                if leader_rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+    };
+    let health_port: u16 = env::var("HEALTH_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(8080);
+    let health_server = health::run(health_port, ctx.clone());
+    // The validating webhook is optional: only run it once TLS material is configured.
+    let webhook_server: std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send>> =
+        match (env::var("WEBHOOK_TLS_CERT"), env::var("WEBHOOK_TLS_KEY")) {
+            (Ok(cert_path), Ok(key_path)) => {
+                let webhook_port: u16 = env::var("WEBHOOK_PORT")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(8443);
+                Box::pin(async move { webhook::run(webhook_port, &cert_path, &key_path).await })
+            }
+            _ => {
+                log::info!("WEBHOOK_TLS_CERT/WEBHOOK_TLS_KEY not set, admission webhook disabled");
+                Box::pin(std::future::pending())
+            }
+        };
     tokio::select! {
        _ = schedule => (),
        _ = controller => (),
+       res = health_server => { res.context("Health server failed")?; },
+       res = webhook_server => { res.context("Webhook server failed")?; },
     };
     Ok(())
 }
@@ -0,0 +1,111 @@
+use crate::{IAMIdentityMapping, IAMIdentityMappingSpec};
+use actix_web::{web, App, HttpResponse, HttpServer};
+use kube::core::admission::{AdmissionRequest, AdmissionResponse, AdmissionReview};
+use regex::Regex;
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::{certs, pkcs8_private_keys};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::OnceLock;
+use tracing::log;
+
+fn role_arn_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^arn:aws[a-zA-Z-]*:iam::\d{12}:role/.+$").unwrap())
+}
+
+fn user_arn_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^arn:aws[a-zA-Z-]*:iam::\d{12}:user/.+$").unwrap())
+}
+
+fn template_placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{[A-Za-z0-9]+\}\}").unwrap())
+}
+
+/// Validates a single `IAMIdentityMapping` spec, returning a human-readable error for the first
+/// problem found.
+fn validate(spec: &IAMIdentityMappingSpec) -> Result<(), String> {
+    if !role_arn_pattern().is_match(&spec.arn) && !user_arn_pattern().is_match(&spec.arn) {
+        return Err(format!(
+            "arn {:?} is not a valid IAM role or user ARN (expected arn:aws:iam::<account>:role/... or :user/...)",
+            spec.arn
+        ));
+    }
+    if spec.username.trim().is_empty() {
+        return Err("username must not be empty".to_string());
+    }
+    // Strip well-formed {{Placeholder}} tokens before checking for stray braces, so templated
+    // usernames like "{{SessionName}}" don't trip a "malformed placeholder" error.
+    let without_placeholders = template_placeholder_pattern().replace_all(&spec.username, "");
+    if without_placeholders.contains("{{") || without_placeholders.contains("}}") {
+        return Err(format!(
+            "username {:?} contains a malformed templated placeholder",
+            spec.username
+        ));
+    }
+    if let Some(groups) = &spec.groups {
+        for group in groups {
+            // Kubernetes group names are free-form strings, not RFC-1123 subdomains: the most
+            // common real-world groups (`system:masters`, `system:nodes`, `system:bootstrappers`)
+            // contain `:` and would be rejected by a subdomain check.
+            if group.is_empty() {
+                return Err("group must not be empty".to_string());
+            }
+            if group.chars().any(|c| c.is_control()) {
+                return Err(format!("group {:?} must not contain control characters", group));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn validate_handler(body: web::Json<AdmissionReview<IAMIdentityMapping>>) -> HttpResponse {
+    let request: AdmissionRequest<IAMIdentityMapping> = match body.into_inner().try_into() {
+        Ok(request) => request,
+        Err(e) => {
+            log::warn!("Invalid AdmissionReview: {}", e);
+            return HttpResponse::Ok().json(AdmissionResponse::invalid(e.to_string()).into_review());
+        }
+    };
+
+    let mut response = AdmissionResponse::from(&request);
+    if let Some(mapping) = &request.object {
+        if let Err(message) = validate(&mapping.spec) {
+            log::info!("Rejecting IAMIdentityMapping admission: {}", message);
+            response = response.deny(message);
+        }
+    }
+    HttpResponse::Ok().json(response.into_review())
+}
+
+/// Runs the `ValidatingWebhookConfiguration` HTTPS server on `port` until the process is stopped.
+/// TLS material is read from `WEBHOOK_TLS_CERT`/`WEBHOOK_TLS_KEY` (PEM files), as mounted from the
+/// Secret referenced by the `ValidatingWebhookConfiguration`.
+pub async fn run(port: u16, cert_path: &str, key_path: &str) -> anyhow::Result<()> {
+    let tls_config = load_tls_config(cert_path, key_path)?;
+    HttpServer::new(|| App::new().route("/validate", web::post().to(validate_handler)))
+        .bind_rustls_021(("0.0.0.0", port), tls_config)?
+        .run()
+        .await?;
+    Ok(())
+}
+
+fn load_tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<ServerConfig> {
+    let cert_chain = certs(&mut BufReader::new(File::open(cert_path)?))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let mut keys: Vec<PrivateKey> = pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))?
+        .into_iter()
+        .map(PrivateKey)
+        .collect();
+    let key = keys
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {}", key_path))?;
+    Ok(ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)?)
+}